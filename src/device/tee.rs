@@ -10,16 +10,31 @@ use crate::{
     Result,
 };
 use bytes::Bytes;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use helium_crypto::{
     tee::{
         self,
-        iotpi_helium_optee::{del_ecc_keypair, gen_ecc_keypair},
+        iotpi_helium_optee::{
+            del_ecc_keypair, gen_ecc_keypair, hardware_unique_key, import_ecc_keypair,
+        },
     },
-    KeyTag, KeyType, Keypair, Network, Sign, Verify,
+    KeyTag, KeyType, Keypair, Network, PublicKey, Sign, Verify,
 };
+use hkdf::Hkdf;
 use http::Uri;
+use p256::{
+    elliptic_curve::{
+        ff::{Field, PrimeField},
+        group::GroupEncoding,
+    },
+    ProjectivePoint, Scalar,
+};
 use rand::rngs::OsRng;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use std::{
     fmt,
     path::{Path, PathBuf},
@@ -28,28 +43,40 @@ use std::{
 #[derive(Debug)]
 pub struct Device {
     pub slot: u8,
+    pub network: Network,
+    pub key_type: KeyType,
 }
 
 impl Device {
-    // tz://iotpi-optee?slot=0
+    // tz://iotpi-optee?slot=0&network=mainnet&key_type=ecc_compact
     pub fn from_url(url: &Uri) -> Result<Self> {
         let args = DeviceArgs::from_uri(url)?;
         let address = url.port_u16().unwrap_or(96);
         let slot = args.get("slot", 0)?;
+        let network = args.get("network", Network::MainNet)?;
+        let key_type = args.get("key_type", KeyType::EccCompact)?;
 
-        Ok(Self { slot })
+        Ok(Self {
+            slot,
+            network,
+            key_type,
+        })
     }
 
     pub fn get_info(&self) -> Result<Info> {
-        let info = Info { slot: self.slot };
+        let info = Info {
+            slot: self.slot,
+            network: self.network,
+            key_type: self.key_type,
+        };
         Ok(info)
     }
 
     pub fn get_keypair(&self, create: bool) -> Result<Keypair> {
         let keypair: Keypair = if create {
-            generate_compact_key_in_slot(self.slot)?
+            generate_compact_key_in_slot(self.slot, self.network, self.key_type)?
         } else {
-            compact_key_in_slot(self.slot)?
+            compact_key_in_slot(self.slot, self.network, self.key_type)?
         };
         Ok(keypair)
     }
@@ -58,30 +85,487 @@ impl Device {
         self.get_keypair(true)
     }
 
+    /// Provisions a single P-256 (`EccCompact`) keypair as `n` Shamir shares, requiring
+    /// `threshold` of them to reconstruct the private scalar. One share is
+    /// stored per TEE slot in `slots`, so that fewer than `threshold`
+    /// compromised slots reveal nothing about the key.
+    pub fn provision_threshold(slots: &[u8], threshold: u8) -> Result<(Keypair, Commitments)> {
+        if threshold == 0 || (threshold as usize) > slots.len() {
+            return Err(anyhow!(
+                "threshold must be between 1 and the number of slots"
+            ));
+        }
+
+        let secret = Scalar::random(&mut OsRng);
+        let (shares, commitments) = split_secret(secret, slots, threshold);
+
+        for (slot, share) in shares {
+            import_ecc_keypair(slot, &share.to_bytes())?;
+        }
+
+        let keypair = keypair_from_scalar(secret)?;
+        Ok((keypair, commitments))
+    }
+
+    /// Reads shares back out of `slots` and reconstructs the original
+    /// private key via Lagrange interpolation at `x = 0`. Requires at least
+    /// `threshold` shares, and verifies each one against `commitments`
+    /// (Feldman's verifiable secret sharing) before interpolating, so an
+    /// insufficient or tampered share set fails loudly instead of silently
+    /// producing the wrong key.
+    pub fn reconstruct(slots: &[u8], threshold: u8, commitments: &Commitments) -> Result<Keypair> {
+        if (slots.len() as u8) < threshold {
+            return Err(anyhow!(
+                "need at least {threshold} shares to reconstruct, got {}",
+                slots.len()
+            ));
+        }
+
+        let mut seen = std::collections::HashSet::with_capacity(slots.len());
+        for &slot in slots {
+            if !seen.insert(slot) {
+                return Err(anyhow!("duplicate slot {slot} in reconstruction set"));
+            }
+        }
+
+        let mut shares = Vec::with_capacity(slots.len());
+        for &slot in slots {
+            let keypair = compact_key_in_slot(slot, Network::MainNet, KeyType::EccCompact)?;
+            let share = scalar_from_keypair(&keypair)?;
+            if !commitments.verify(slot, share) {
+                return Err(anyhow!("share in slot {slot} failed Feldman commitment check"));
+            }
+            shares.push((slot, share));
+        }
+
+        let secret = lagrange_interpolate(&shares)?;
+        keypair_from_scalar(secret)
+    }
+
+    /// Returns the public key of this TEE's DICE root-of-trust layer, the
+    /// value an onboarding server must pin (e.g. by recording it out of band
+    /// during provisioning) in order to call [`verify_attestation`] on a
+    /// chain produced by this device later, without needing TEE access of
+    /// its own. Only supported for `KeyType::EccCompact` slots; see
+    /// [`Device::attest`].
+    pub fn attestation_root_key(&self) -> Result<PublicKey> {
+        require_ecc_compact(self.key_type)?;
+        Ok(attestation_root_keypair()?.public_key().clone())
+    }
+
+    /// Builds a DICE-style attestation chain proving the miner key in
+    /// `self.slot` was generated and lives inside the TEE: a self-signed
+    /// root-of-trust layer, followed by a TEE layer derived from the TEE's
+    /// measurement, followed by a leaf layer covering the miner key itself.
+    /// Returns the CBOR-encoded cert chain.
+    ///
+    /// Only supported for `KeyType::EccCompact` slots: the DICE layer
+    /// keypairs are derived over the same P-256 scalar field as the miner
+    /// key, which has no equivalent for `KeyType::Ed25519`.
+    pub fn attest(&self) -> Result<Bytes> {
+        require_ecc_compact(self.key_type)?;
+        let miner_keypair = compact_key_in_slot(self.slot, self.network, self.key_type)?;
+
+        let root_keypair = attestation_root_keypair()?;
+        let root_cert = Cert::signed(
+            &root_keypair,
+            "root-of-trust",
+            root_keypair.public_key(),
+            TEE_ROOT_MEASUREMENT,
+        )?;
+
+        let tee_cdi = derive_cdi(TEE_MEASUREMENT, root_keypair.public_key().as_ref())?;
+        let tee_keypair = cdi_keypair(&tee_cdi)?;
+        let tee_cert = Cert::signed(
+            &root_keypair,
+            "root-of-trust",
+            tee_keypair.public_key(),
+            TEE_MEASUREMENT,
+        )?;
+
+        let leaf_cert = Cert::signed(
+            &tee_keypair,
+            &tee_keypair.public_key().to_string(),
+            miner_keypair.public_key(),
+            b"miner_key",
+        )?;
+
+        let chain = vec![root_cert, tee_cert, leaf_cert];
+        Ok(Bytes::from(serde_cbor::to_vec(&chain)?))
+    }
+
+    /// Seals `plaintext` to `recipient` using ECIES: an ephemeral keypair's
+    /// ECDH shared secret with `recipient` is passed through HKDF-SHA256 to
+    /// derive a ChaCha20-Poly1305 key and nonce. The result is
+    /// `ephemeral_pubkey_len || ephemeral_pubkey || nonce || ciphertext`,
+    /// where `ciphertext` includes the AEAD tag.
+    pub fn encrypt(recipient: &PublicKey, plaintext: &[u8]) -> Result<Bytes> {
+        let ephemeral_keypair = Keypair::generate(
+            KeyTag {
+                network: Network::MainNet,
+                key_type: KeyType::EccCompact,
+            },
+            &mut OsRng,
+        );
+        let shared_secret = ephemeral_keypair.ecdh(recipient)?;
+        let (key, nonce) = derive_seal_key(shared_secret.as_bytes())?;
+
+        let cipher = ChaCha20Poly1305::new(&key);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("failed to seal plaintext"))?;
+
+        let ephemeral_public_key = ephemeral_keypair.public_key().as_ref();
+        let mut sealed =
+            Vec::with_capacity(1 + ephemeral_public_key.len() + NONCE_LEN + ciphertext.len());
+        sealed.push(ephemeral_public_key.len() as u8);
+        sealed.extend_from_slice(ephemeral_public_key);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(Bytes::from(sealed))
+    }
+
+    /// Opens a sealed box produced by [`Device::encrypt`] for the public key
+    /// held in `self.slot`, using that slot's private key. Only supported
+    /// for `KeyType::EccCompact` slots, since sealing relies on ECDH, which
+    /// `KeyType::Ed25519` keys don't support.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Bytes> {
+        require_ecc_compact(self.key_type)?;
+        let (&key_len, rest) = ciphertext
+            .split_first()
+            .ok_or_else(|| anyhow!("sealed box too short"))?;
+        let key_len = key_len as usize;
+        if rest.len() < key_len + NONCE_LEN {
+            return Err(anyhow!("sealed box too short"));
+        }
+        let (ephemeral_public_key, rest) = rest.split_at(key_len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let ephemeral_public_key = PublicKey::try_from(ephemeral_public_key)?;
+        let nonce = *Nonce::from_slice(nonce);
+
+        let keypair = compact_key_in_slot(self.slot, self.network, self.key_type)?;
+        let shared_secret = keypair.ecdh(&ephemeral_public_key)?;
+        let (key, _) = derive_seal_key(shared_secret.as_bytes())?;
+
+        let cipher = ChaCha20Poly1305::new(&key);
+        let plaintext = cipher
+            .decrypt(&nonce, ciphertext)
+            .map_err(|_| anyhow!("failed to open sealed box"))?;
+        Ok(Bytes::from(plaintext))
+    }
+
+    /// Signs `challenge` inside the TEE and returns a proof binding the
+    /// slot's public key to that externally chosen value, so the result
+    /// resists replay unlike the fixed message [`check_sign`] verifies.
+    pub fn prove(&self, challenge: &[u8]) -> Result<Proof> {
+        let keypair = compact_key_in_slot(self.slot, self.network, self.key_type)?;
+        let signature = keypair.sign(challenge)?;
+        Ok(Proof {
+            public_key: keypair.public_key().clone(),
+            challenge: Bytes::from(challenge.to_vec()),
+            signature: Bytes::from(signature),
+        })
+    }
+
+    /// Verifies that `proof.signature` is a valid signature by
+    /// `proof.public_key` over `proof.challenge`. This only checks the
+    /// proof's internal consistency; callers still need to compare
+    /// `proof.public_key` against the public key they expect for the
+    /// gateway before trusting the proof as evidence of possession.
+    pub fn verify_proof(proof: &Proof) -> Result<()> {
+        proof.public_key.verify(&proof.challenge, &proof.signature)?;
+        Ok(())
+    }
+
     pub fn get_config(&self) -> Result<Config> {
         Ok(Config {})
     }
 
     pub fn get_tests(&self) -> Vec<Test> {
         vec![
-            Test::MinerKey(self.slot),
-            Test::Sign(self.slot),
-            Test::Ecdh(self.slot),
+            Test::MinerKey(self.slot, self.network, self.key_type),
+            Test::Sign(self.slot, self.network, self.key_type),
+            Test::Ecdh(self.slot, self.network, self.key_type),
+            Test::Threshold(threshold_test_slots(self.slot), 2),
+            Test::Attestation(self.slot, self.network, self.key_type),
+            Test::Seal(self.slot, self.network, self.key_type),
+            Test::Possession(self.slot, self.network, self.key_type),
         ]
     }
 }
 
-fn compact_key_in_slot(slot: u8) -> Result<Keypair> {
-    let keypair = tee::Keypair::keypair(slot, Network::MainNet)?;
+/// Candidate scratch slots for the threshold self-test, preferred in order.
+/// `slot` is an arbitrary user-supplied value from the `tz://` URL, so these
+/// are filtered against the gateway's own identity slot in
+/// [`threshold_test_slots`] rather than assumed disjoint from it.
+const THRESHOLD_TEST_SLOT_CANDIDATES: [u8; 4] = [253, 254, 255, 252];
+
+/// Picks 3 scratch slots for the threshold self-test that exclude
+/// `own_slot`, so that running the self tests never clobbers the
+/// provisioned miner key with a Shamir share even if the gateway happens to
+/// be provisioned on one of the default scratch slots.
+fn threshold_test_slots(own_slot: u8) -> Vec<u8> {
+    THRESHOLD_TEST_SLOT_CANDIDATES
+        .into_iter()
+        .filter(|&slot| slot != own_slot)
+        .take(3)
+        .collect()
+}
+
+/// Proof that the keypair in a slot exists and can sign, bound to a
+/// server-supplied challenge. Returned by [`Device::prove`] so an onboarding
+/// service can verify possession without trusting a bare public key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Proof {
+    public_key: PublicKey,
+    challenge: Bytes,
+    signature: Bytes,
+}
+
+const NONCE_LEN: usize = 12;
+
+/// Derives a ChaCha20-Poly1305 key and nonce from an ECDH shared secret via
+/// HKDF-SHA256.
+fn derive_seal_key(shared_secret: &[u8]) -> Result<(Key, Nonce)> {
+    let hk = Hkdf::<Sha256>::new(None, shared_secret);
+    let mut okm = [0u8; 32 + NONCE_LEN];
+    hk.expand(b"gateway-mfr-rs sealed-box", &mut okm)
+        .map_err(|_| anyhow!("failed to derive sealed-box key"))?;
+    let key = *Key::from_slice(&okm[..32]);
+    let nonce = *Nonce::from_slice(&okm[32..]);
+    Ok((key, nonce))
+}
+
+/// A single CBOR-encoded, CWT-style certificate in a DICE attestation chain.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cert {
+    issuer: String,
+    subject: String,
+    subject_public_key: Bytes,
+    config: Bytes,
+    signature: Bytes,
+}
+
+impl Cert {
+    fn signed(
+        issuer_keypair: &Keypair,
+        issuer: &str,
+        subject_public_key: &PublicKey,
+        config: &[u8],
+    ) -> Result<Self> {
+        let subject = subject_public_key.to_string();
+        let subject_public_key = Bytes::from(subject_public_key.as_ref().to_vec());
+        let config = Bytes::from(config.to_vec());
+        let payload = Self::signing_payload(issuer, &subject, &subject_public_key, &config);
+        let signature = Bytes::from(issuer_keypair.sign(&payload)?);
+        Ok(Self {
+            issuer: issuer.to_string(),
+            subject,
+            subject_public_key,
+            config,
+            signature,
+        })
+    }
+
+    fn signing_payload(
+        issuer: &str,
+        subject: &str,
+        subject_public_key: &Bytes,
+        config: &Bytes,
+    ) -> Vec<u8> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(issuer.as_bytes());
+        payload.extend_from_slice(subject.as_bytes());
+        payload.extend_from_slice(subject_public_key);
+        payload.extend_from_slice(config);
+        payload
+    }
+
+    fn subject_public_key(&self) -> Result<PublicKey> {
+        PublicKey::try_from(self.subject_public_key.as_ref())
+            .map_err(|err| anyhow!("invalid attestation subject public key: {err}"))
+    }
+
+    fn verify(&self, issuer_public_key: &PublicKey) -> Result<()> {
+        let payload = Self::signing_payload(
+            &self.issuer,
+            &self.subject,
+            &self.subject_public_key,
+            &self.config,
+        );
+        issuer_public_key.verify(&payload, &self.signature)?;
+        Ok(())
+    }
+}
+
+const TEE_ROOT_MEASUREMENT: &[u8] = b"iotpi-optee-root-of-trust";
+const TEE_MEASUREMENT: &[u8] = b"iotpi-optee-tee-layer";
+
+/// Derives a Compound Device Identifier by hashing the TEE's hardware unique
+/// key together with a layer measurement and the parent layer's public key,
+/// per the open-dice model. Folding in the hardware unique key means the
+/// chain can only be produced by this specific TEE, not recomputed from the
+/// source alone.
+fn derive_cdi(measurement: &[u8], parent_public_key: &[u8]) -> Result<[u8; 32]> {
+    let huk = hardware_unique_key()?;
+    let mut hasher = Sha256::new();
+    hasher.update(huk);
+    hasher.update(measurement);
+    hasher.update(parent_public_key);
+    Ok(hasher.finalize().into())
+}
+
+/// Derives a deterministic keypair for a DICE layer from its CDI.
+fn cdi_keypair(cdi: &[u8; 32]) -> Result<Keypair> {
+    let scalar = Option::<Scalar>::from(Scalar::from_repr((*cdi).into()))
+        .ok_or_else(|| anyhow!("invalid CDI scalar"))?;
+    keypair_from_scalar(scalar)
+}
+
+/// Derives this TEE's DICE root-of-trust keypair. Deterministic for a given
+/// device (it folds in the hardware unique key), but not parented on
+/// anything else, so it's the root of the chain rather than a layer of it.
+fn attestation_root_keypair() -> Result<Keypair> {
+    cdi_keypair(&derive_cdi(TEE_ROOT_MEASUREMENT, &[])?)
+}
+
+/// Walks an attestation `chain` produced by [`Device::attest`], checking
+/// that the root cert's subject matches the pinned `root_public_key` (e.g.
+/// one captured via [`Device::attestation_root_key`] during provisioning)
+/// and that each subsequent cert's signature verifies against its issuer's
+/// public key. Returns the leaf subject's public key, which the caller must
+/// compare against the miner public key it expects for the gateway — unlike [`Device::attest`], this takes no TEE access, so a
+/// remote onboarding server can call it without a `Device` of its own.
+pub fn verify_attestation(chain: &[u8], root_public_key: &PublicKey) -> Result<PublicKey> {
+    let certs: Vec<Cert> = serde_cbor::from_slice(chain)?;
+    let (root, rest) = certs
+        .split_first()
+        .ok_or_else(|| anyhow!("empty attestation chain"))?;
+    let root_subject_key = root.subject_public_key()?;
+    if &root_subject_key != root_public_key {
+        return Err(anyhow!(
+            "attestation root does not match pinned root public key"
+        ));
+    }
+    root.verify(&root_subject_key)?;
+
+    let mut issuer_key = root_subject_key;
+    for cert in rest {
+        cert.verify(&issuer_key)?;
+        issuer_key = cert.subject_public_key()?;
+    }
+    Ok(issuer_key)
+}
+
+/// Public per-coefficient commitments `g^{a_j}` for a Shamir split, letting a
+/// holder of share `(i, f(i))` verify `f(i)` without learning any other
+/// share or the secret itself (Feldman's verifiable secret sharing).
+#[derive(Debug, Clone)]
+pub struct Commitments(Vec<ProjectivePoint>);
+
+impl Commitments {
+    /// Verifies that `share` is consistent with `f(slot + 1)` (see
+    /// [`shamir_index`]) for the polynomial these commitments were derived
+    /// from.
+    pub fn verify(&self, slot: u8, share: Scalar) -> bool {
+        let lhs = ProjectivePoint::GENERATOR * share;
+        let x = shamir_index(slot);
+        let mut rhs = ProjectivePoint::IDENTITY;
+        let mut x_pow = Scalar::ONE;
+        for commitment in &self.0 {
+            rhs += *commitment * x_pow;
+            x_pow *= x;
+        }
+        lhs == rhs
+    }
+}
+
+/// Maps a TEE slot number to its Shamir x-coordinate. `f(0) == secret`, so
+/// slot `0` must never be used as-is for `x` or it would hand out the whole
+/// secret instead of a share; indices are therefore `slot + 1`, starting
+/// at `1`.
+fn shamir_index(slot: u8) -> Scalar {
+    Scalar::from(slot as u64 + 1)
+}
+
+/// Splits `secret` into shares `(slot, f(slot + 1))` for each slot in `slots`
+/// (see [`shamir_index`]) using a random
+/// degree-`threshold - 1` polynomial `f` with `f(0) == secret`, returning the
+/// Feldman commitments to each coefficient alongside the shares.
+fn split_secret(secret: Scalar, slots: &[u8], threshold: u8) -> (Vec<(u8, Scalar)>, Commitments) {
+    let mut coefficients = Vec::with_capacity(threshold as usize);
+    coefficients.push(secret);
+    for _ in 1..threshold {
+        coefficients.push(Scalar::random(&mut OsRng));
+    }
+
+    let commitments = coefficients
+        .iter()
+        .map(|coefficient| ProjectivePoint::GENERATOR * coefficient)
+        .collect();
+
+    let shares = slots
+        .iter()
+        .map(|&slot| {
+            let x = shamir_index(slot);
+            let mut value = Scalar::ZERO;
+            let mut x_pow = Scalar::ONE;
+            for coefficient in &coefficients {
+                value += coefficient * &x_pow;
+                x_pow *= x;
+            }
+            (slot, value)
+        })
+        .collect();
+
+    (shares, Commitments(commitments))
+}
+
+/// Recovers `f(0)` from `shares` via Lagrange interpolation over the scalar
+/// field.
+fn lagrange_interpolate(shares: &[(u8, Scalar)]) -> Result<Scalar> {
+    let mut secret = Scalar::ZERO;
+    for (i, (slot_i, yi)) in shares.iter().enumerate() {
+        let mut numerator = Scalar::ONE;
+        let mut denominator = Scalar::ONE;
+        let xi = shamir_index(*slot_i);
+        for (j, (slot_j, _)) in shares.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let xj = shamir_index(*slot_j);
+            numerator *= xj;
+            denominator *= xj - xi;
+        }
+        let denominator_inv = Option::<Scalar>::from(denominator.invert())
+            .ok_or_else(|| anyhow!("duplicate slot {slot_i} in Shamir reconstruction"))?;
+        secret += yi * &numerator * &denominator_inv;
+    }
+    Ok(secret)
+}
+
+/// Rejects key types that the P-256 scalar math backing attestation and
+/// sealed-box encryption can't represent.
+fn require_ecc_compact(key_type: KeyType) -> Result<()> {
+    if key_type != KeyType::EccCompact {
+        return Err(anyhow!(
+            "{key_type} keys are not supported for this operation, only EccCompact"
+        ));
+    }
+    Ok(())
+}
+
+fn compact_key_in_slot(slot: u8, network: Network, key_type: KeyType) -> Result<Keypair> {
+    let keypair = tee::Keypair::keypair(slot, network, key_type)?;
     Ok(keypair.into())
 }
 
-fn generate_compact_key_in_slot(slot: u8) -> Result<Keypair> {
+fn generate_compact_key_in_slot(slot: u8, network: Network, key_type: KeyType) -> Result<Keypair> {
     let mut try_count = 10;
     loop {
-        gen_ecc_keypair(slot)?;
+        gen_ecc_keypair(slot, key_type)?;
 
-        match compact_key_in_slot(slot) {
+        match compact_key_in_slot(slot, network, key_type) {
             Ok(keypair) => return Ok(keypair),
             Err(err) if try_count == 0 => return Err(err),
             Err(_) => {
@@ -92,9 +576,35 @@ fn generate_compact_key_in_slot(slot: u8) -> Result<Keypair> {
     }
 }
 
+fn keypair_from_scalar(scalar: Scalar) -> Result<Keypair> {
+    let secret = helium_crypto::ecc_compact::SecretKey::try_from(&scalar.to_bytes()[..])?;
+    Ok(Keypair::from(helium_crypto::ecc_compact::Keypair::from((
+        Network::MainNet,
+        secret,
+    ))))
+}
+
+fn scalar_from_keypair(keypair: &Keypair) -> Result<Scalar> {
+    let ecc_compact: &helium_crypto::ecc_compact::Keypair = keypair.try_into()?;
+    let bytes = ecc_compact.secret.to_bytes();
+    Option::<Scalar>::from(Scalar::from_repr(bytes.into()))
+        .ok_or_else(|| anyhow!("invalid scalar"))
+}
+
 #[derive(Debug, Serialize)]
 pub struct Info {
     slot: u8,
+    #[serde(serialize_with = "serialize_display")]
+    network: Network,
+    #[serde(serialize_with = "serialize_display")]
+    key_type: KeyType,
+}
+
+fn serialize_display<T: fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
 }
 
 #[derive(Debug, Serialize)]
@@ -102,17 +612,41 @@ pub struct Config {}
 
 #[derive(Debug)]
 pub enum Test {
-    MinerKey(u8),
-    Sign(u8),
-    Ecdh(u8),
+    MinerKey(u8, Network, KeyType),
+    Sign(u8, Network, KeyType),
+    Ecdh(u8, Network, KeyType),
+    Threshold(Vec<u8>, u8),
+    Attestation(u8, Network, KeyType),
+    Seal(u8, Network, KeyType),
+    Possession(u8, Network, KeyType),
 }
 
 impl fmt::Display for Test {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::MinerKey(slot) => f.write_fmt(format_args!("miner_key({})", slot)),
-            Self::Sign(slot) => f.write_fmt(format_args!("sign({})", slot)),
-            Self::Ecdh(slot) => f.write_fmt(format_args!("ecdh({})", slot)),
+            Self::MinerKey(slot, network, key_type) => {
+                f.write_fmt(format_args!("miner_key({}, {}, {})", slot, network, key_type))
+            }
+            Self::Sign(slot, network, key_type) => {
+                f.write_fmt(format_args!("sign({}, {}, {})", slot, network, key_type))
+            }
+            Self::Ecdh(slot, network, key_type) => {
+                f.write_fmt(format_args!("ecdh({}, {}, {})", slot, network, key_type))
+            }
+            Self::Threshold(slots, threshold) => {
+                f.write_fmt(format_args!("threshold({:?}, {})", slots, threshold))
+            }
+            Self::Attestation(slot, network, key_type) => f.write_fmt(format_args!(
+                "attestation({}, {}, {})",
+                slot, network, key_type
+            )),
+            Self::Seal(slot, network, key_type) => {
+                f.write_fmt(format_args!("seal({}, {}, {})", slot, network, key_type))
+            }
+            Self::Possession(slot, network, key_type) => f.write_fmt(format_args!(
+                "possession({}, {}, {})",
+                slot, network, key_type
+            )),
         }
     }
 }
@@ -120,35 +654,37 @@ impl fmt::Display for Test {
 impl Test {
     pub fn run(&self) -> TestResult {
         match self {
-            Self::MinerKey(slot) => check_miner_key(*slot),
-            Self::Sign(slot) => check_sign(*slot),
-            Self::Ecdh(slot) => check_ecdh(*slot),
+            Self::MinerKey(slot, network, key_type) => check_miner_key(*slot, *network, *key_type),
+            Self::Sign(slot, network, key_type) => check_sign(*slot, *network, *key_type),
+            Self::Ecdh(slot, network, key_type) => check_ecdh(*slot, *network, *key_type),
+            Self::Threshold(slots, threshold) => check_threshold(slots, *threshold),
+            Self::Attestation(slot, network, key_type) => {
+                check_attestation(*slot, *network, *key_type)
+            }
+            Self::Seal(slot, network, key_type) => check_seal(*slot, *network, *key_type),
+            Self::Possession(slot, network, key_type) => {
+                check_possession(*slot, *network, *key_type)
+            }
         }
     }
 }
-fn check_miner_key(slot: u8) -> TestResult {
-    let keypair = compact_key_in_slot(slot)?;
+fn check_miner_key(slot: u8, network: Network, key_type: KeyType) -> TestResult {
+    let keypair = compact_key_in_slot(slot, network, key_type)?;
     test::pass(keypair.public_key()).into()
 }
 
-fn check_sign(slot: u8) -> TestResult {
+fn check_sign(slot: u8, network: Network, key_type: KeyType) -> TestResult {
     const DATA: &[u8] = b"hello world";
-    let keypair = compact_key_in_slot(slot)?;
+    let keypair = compact_key_in_slot(slot, network, key_type)?;
     let signature = keypair.sign(DATA)?;
     keypair.public_key().verify(DATA, &signature)?;
     test::pass("ok").into()
 }
 
-fn check_ecdh(slot: u8) -> TestResult {
+fn check_ecdh(slot: u8, network: Network, key_type: KeyType) -> TestResult {
     use rand::rngs::OsRng;
-    let keypair = compact_key_in_slot(slot)?;
-    let other_keypair = Keypair::generate(
-        KeyTag {
-            network: Network::MainNet,
-            key_type: KeyType::EccCompact,
-        },
-        &mut OsRng,
-    );
+    let keypair = compact_key_in_slot(slot, network, key_type)?;
+    let other_keypair = Keypair::generate(KeyTag { network, key_type }, &mut OsRng);
     let ecc_shared_secret = keypair.ecdh(other_keypair.public_key())?;
     let other_shared_secret = other_keypair.ecdh(&keypair.public_key())?;
 
@@ -161,3 +697,92 @@ fn check_ecdh(slot: u8) -> TestResult {
     }
     test::pass("ok").into()
 }
+
+fn check_attestation(slot: u8, network: Network, key_type: KeyType) -> TestResult {
+    let device = Device {
+        slot,
+        network,
+        key_type,
+    };
+    let root_key = device.attestation_root_key()?;
+    let chain = device.attest()?;
+    let leaf_key = verify_attestation(&chain, &root_key)?;
+
+    let miner_keypair = compact_key_in_slot(slot, network, key_type)?;
+    if &leaf_key != miner_keypair.public_key() {
+        return test::expected(miner_keypair.public_key().to_string(), leaf_key.to_string()).into();
+    }
+    test::pass("ok").into()
+}
+
+fn check_seal(slot: u8, network: Network, key_type: KeyType) -> TestResult {
+    const DATA: &[u8] = b"hello world";
+    let device = Device {
+        slot,
+        network,
+        key_type,
+    };
+    let keypair = compact_key_in_slot(slot, network, key_type)?;
+
+    let sealed = Device::encrypt(keypair.public_key(), DATA)?;
+    let opened = device.decrypt(&sealed)?;
+
+    if opened.as_ref() != DATA {
+        return test::expected(
+            format!("{:#02x}", Bytes::from_static(DATA)),
+            format!("{:#02x}", opened),
+        )
+        .into();
+    }
+    test::pass("ok").into()
+}
+
+fn check_possession(slot: u8, network: Network, key_type: KeyType) -> TestResult {
+    let device = Device {
+        slot,
+        network,
+        key_type,
+    };
+    let challenge: [u8; 32] = rand::random();
+    let proof = device.prove(&challenge)?;
+    Device::verify_proof(&proof)?;
+
+    let keypair = compact_key_in_slot(slot, network, key_type)?;
+    if &proof.public_key != keypair.public_key() {
+        return test::expected(
+            keypair.public_key().to_string(),
+            proof.public_key.to_string(),
+        )
+        .into();
+    }
+    if proof.challenge.as_ref() != challenge {
+        return test::expected(
+            format!("{:#02x}", Bytes::copy_from_slice(&challenge)),
+            format!("{:#02x}", proof.challenge),
+        )
+        .into();
+    }
+    test::pass("ok").into()
+}
+
+fn check_threshold(slots: &[u8], threshold: u8) -> TestResult {
+    let (keypair, commitments) = Device::provision_threshold(slots, threshold)?;
+
+    // Reconstruction should still succeed, and yield the same public key,
+    // after dropping a random share down to exactly `threshold` of them.
+    let mut remaining = slots.to_vec();
+    while remaining.len() > threshold as usize {
+        let drop_at = (rand::random::<u8>() as usize) % remaining.len();
+        remaining.remove(drop_at);
+    }
+    let reconstructed = Device::reconstruct(&remaining, threshold, &commitments)?;
+
+    if reconstructed.public_key() != keypair.public_key() {
+        return test::expected(
+            keypair.public_key().to_string(),
+            reconstructed.public_key().to_string(),
+        )
+        .into();
+    }
+    test::pass("ok").into()
+}